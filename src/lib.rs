@@ -3,7 +3,7 @@
 
 #![deny(missing_docs)]
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::marker::{PhantomData, Unpin};
@@ -14,10 +14,14 @@ use actix::prelude::*;
 use diesel::associations::HasTable;
 use diesel::connection::Connection;
 use diesel::deserialize::Queryable;
+use diesel::dsl::{Asc, Filter, Find, Gt, Limit};
+use diesel::expression::{AppearsOnTable, AsExpression};
 use diesel::insertable::CanInsertInSingleQuery;
 use diesel::prelude::*;
-use diesel::query_builder::{AsQuery, QueryFragment, QueryId};
+use diesel::query_builder::{AsQuery, BoxedSelectStatement, QueryFragment, QueryId};
+use diesel::query_dsl::methods::{BoxedDsl, FilterDsl, FindDsl, LimitDsl, LoadQuery};
 use diesel::sql_types::HasSqlType;
+use diesel::{Column, Expression};
 
 /// Error of cache actor
 pub type Error = diesel::result::Error;
@@ -38,11 +42,30 @@ where
     Table::Query: QueryId + QueryFragment<Conn::Backend>,
 {
     /// Id type for getting specific records
-    type Id: Hash + Eq + Clone;
+    ///
+    /// `Unpin` because [`CacheDbActor`] stores it unwrapped in both
+    /// `recency: VecDeque<Self::Id>` and `indexes: HashMap<_, Vec<Self::Id>>`,
+    /// and the actor itself must be `Unpin` to implement `Actor`.
+    type Id: Hash + Eq + Clone + Unpin;
+
+    /// Monotonic cursor (e.g. an auto-increment id or `updated_at`) used by
+    /// [`Self::read_since`] to fetch only rows added since the last refresh.
+    ///
+    /// `Unpin` because [`CacheDbActor`] stores it unwrapped in `cursor:
+    /// Option<Self::Cursor>`, and the actor itself must be `Unpin` to
+    /// implement `Actor`.
+    type Cursor: Ord + Copy + Unpin;
+
+    /// Column backing [`Self::Cursor`], used to build the `WHERE cursor >
+    /// ?` filter in [`Self::read_since`].
+    type CursorColumn: Column<Table = Table> + ExpressionMethods + Default;
 
     /// Get id of item
     fn get_id(&self) -> Self::Id;
 
+    /// Get this item's cursor value
+    fn cursor(&self) -> Self::Cursor;
+
     /// Read all entries from db
     fn read_all(c: &Conn) -> Result<HashMap<Self::Id, Self>> {
         let vec: Vec<Self> = Table::table().load(c)?;
@@ -67,22 +90,281 @@ where
     {
         diesel::insert_into(Table::table()).values(w).execute(c)
     }
+
+    /// Read a single entry from db by id.
+    ///
+    /// Used by [`CacheSize::Bounded`] caches to fetch just the missing row
+    /// on a `Get` miss instead of reloading the whole table.
+    fn read_one(id: Self::Id, c: &Conn) -> Result<Option<Self>>
+    where
+        Table: FindDsl<Self::Id>,
+        Find<Table, Self::Id>: LimitDsl,
+        Limit<Find<Table, Self::Id>>: QueryFragment<Conn::Backend>
+            + QueryId
+            + LoadQuery<Conn, Self>,
+    {
+        Table::table().find(id).first(c).optional()
+    }
+
+    /// Read entries added since `cursor`, ordered by cursor ascending.
+    ///
+    /// Used by the actor's timer refresh to apply an incremental delta
+    /// instead of reloading the whole table every tick.
+    ///
+    /// Built on a boxed query rather than `Table`'s own `Filter`/`Order`
+    /// combinators: chaining those generically (`Filter<Table, Gt<..>>:
+    /// OrderDsl<..>`) over an abstract `Table` makes the compiler expand
+    /// the combined type on every call site that needs to prove it (here,
+    /// `CacheDbActor`'s `Actor` impl and every `Handler` impl, since they
+    /// all require `Self: Actor`), which overflows trait resolution.
+    /// `.into_boxed()` collapses `.filter()`/`.order()` back down to the
+    /// same `BoxedSelectStatement` type instead of growing it, so there's
+    /// nothing left to recurse on.
+    fn read_since(cursor: Self::Cursor, c: &Conn) -> Result<Vec<Self>>
+    where
+        Self::Cursor: AsExpression<<Self::CursorColumn as Expression>::SqlType>,
+        Table: BoxedDsl<
+            'static,
+            Conn::Backend,
+            Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+        >,
+        Gt<Self::CursorColumn, Self::Cursor>:
+            AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+        Asc<Self::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+        BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>:
+            LoadQuery<Conn, Self>,
+    {
+        Table::table()
+            .into_boxed()
+            .filter(Self::CursorColumn::default().gt(cursor))
+            .order(Self::CursorColumn::default().asc())
+            .load(c)
+    }
+}
+
+/// Hook for a secondary-index key extracted from a cached row.
+///
+/// Implement this once per key type `K` a caller wants to look rows up by
+/// beyond [`Cache::Id`] (e.g. once for an `Email`, once for a `UserId`);
+/// [`CacheDbActor`] then maintains a `K -> [Id]` index alongside the main
+/// cache and serves [`GetBy`] from it.
+pub trait Indexed<Conn, Table, K>: Cache<Conn, Table>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    K: Hash + Eq + Clone,
+{
+    /// Column backing `K`, used to build the `WHERE column = ?` fallback
+    /// query on an index miss.
+    type KeyColumn: Column<Table = Table> + ExpressionMethods + Default;
+
+    /// Extract this row's index key.
+    fn index_key(&self) -> K;
+
+    /// Read all entries matching `key` straight from db.
+    fn read_by(key: K, c: &Conn) -> Result<Vec<Self>>
+    where
+        K: AsExpression<<Self::KeyColumn as Expression>::SqlType>,
+        Table: FilterDsl<diesel::dsl::Eq<Self::KeyColumn, K>>,
+        Filter<Table, diesel::dsl::Eq<Self::KeyColumn, K>>:
+            QueryFragment<Conn::Backend> + QueryId + LoadQuery<Conn, Self>,
+    {
+        Table::table()
+            .filter(Self::KeyColumn::default().eq(key))
+            .load(c)
+    }
+}
+
+/// Marker secondary-index key used when a [`CacheDbActor`] has no
+/// [`Indexed`] key configured, so `GetBy` is not available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct NoIndex;
+
+/// Gets all entries whose [`Indexed`] key equals `key`.
+#[derive(Debug)]
+pub struct GetBy<Conn, Table, C, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    K: Hash + Eq + Clone,
+{
+    /// Index key to look up
+    pub key: K,
+    _c: PhantomData<(Conn, Table, C)>,
+}
+
+impl<Conn, Table, C, K> GetBy<Conn, Table, C, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    K: Hash + Eq + Clone,
+{
+    /// Constructor
+    pub fn new(key: K) -> Self {
+        Self {
+            key,
+            _c: PhantomData,
+        }
+    }
+}
+
+impl<Conn, Table, C, K> Clone for GetBy<Conn, Table, C, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    K: Hash + Eq + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            _c: PhantomData,
+        }
+    }
+}
+
+impl<Conn, Table, C, K> actix::Message for GetBy<Conn, Table, C, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    K: Hash + Eq + Clone,
+{
+    type Result = Result<Vec<C>>;
+}
+
+/// Controls how much of the table [`CacheDbActor`] keeps in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheSize {
+    /// Cache the whole table, as before. Good for small reference tables.
+    Unbounded,
+    /// Cache nothing; every `Get` reads straight from the db. Useful for
+    /// testing.
+    Disabled,
+    /// Keep at most `n` entries, evicting the least recently used one when
+    /// a fresh entry would push the cache over that size.
+    ///
+    /// Under this mode `self.cache` only ever holds an arbitrary subset of
+    /// the table, never the whole thing: [`GetBy`] must not build its
+    /// secondary index from it, and always queries the db directly
+    /// instead (see [`CacheDbActor`]'s `Handler<GetBy<_>>`).
+    Bounded(usize),
+}
+
+/// Whether `self.cache` under `cache_size` is guaranteed to mirror the
+/// whole table (possibly lagged), as opposed to an arbitrary subset of
+/// it. Only [`CacheSize::Unbounded`] gives that guarantee, so this is
+/// what [`GetBy`] checks before trusting its in-memory secondary index.
+fn cache_is_complete(cache_size: CacheSize) -> bool {
+    matches!(cache_size, CacheSize::Unbounded)
+}
+
+/// Whether an index lookup's result is trustworthy enough to return as-is,
+/// as opposed to falling back to a direct db query: a `None` (nothing
+/// indexed for this key, or no index built yet) and an empty `Vec` (index
+/// entries whose rows are no longer cached) are both treated as a miss, not
+/// as "zero matches".
+fn index_hit<T>(lookup: Option<Vec<T>>) -> Option<Vec<T>> {
+    match lookup {
+        Some(out) if !out.is_empty() => Some(out),
+        _ => None,
+    }
+}
+
+/// Abstraction over a connection pool (as in the bb8-postgres / r2d2
+/// `ConnectionManager` setups used by other Diesel apps).
+///
+/// Implementing this over a pool lets [`CacheDbActor`] check out a
+/// connection per operation instead of serializing every `read_all` /
+/// `write_one` through one connection held for the actor's whole lifetime.
+pub trait PoolLike<Conn>
+where
+    Conn: Connection + Unpin + 'static,
+{
+    /// Connection checked out of the pool. Derefs to `Conn` so it can be
+    /// used anywhere a `&Conn` is expected.
+    type PooledConn: std::ops::Deref<Target = Conn>;
+
+    /// Check out a connection from the pool.
+    fn get(&self) -> Result<Self::PooledConn>;
+}
+
+/// Marker [`PoolLike`] used when [`CacheDbActor`] owns its connection
+/// directly (via [`CacheDbActor::new`]) instead of pulling it from a pool.
+///
+/// Never constructed; `get` is never called because the actor's [`Owned`]
+/// connection source does not go through it.
+///
+/// [`Owned`]: ConnSource::Owned
+#[derive(Debug)]
+pub struct NoPool<Conn>(PhantomData<Conn>);
+
+impl<Conn> PoolLike<Conn> for NoPool<Conn>
+where
+    Conn: Connection + Unpin + 'static,
+{
+    type PooledConn = Box<Conn>;
+
+    fn get(&self) -> Result<Self::PooledConn> {
+        unreachable!("NoPool::get is never called for an owned connection")
+    }
+}
+
+/// Where a [`CacheDbActor`] gets its `&Conn` from for a given operation.
+enum ConnSource<Conn, P> {
+    /// Connection owned by the actor for its whole lifetime.
+    Owned(Conn),
+    /// Connection checked out of a pool per operation.
+    Pool(P),
 }
 
 /// Actix Actor for caching database.
 /// Has fast reads and slow writes. Updates its records once in a minute and on inserts.
-pub struct CacheDbActor<Conn, Table, C>
+/// Under [`CacheSize::Disabled`] and [`CacheSize::Bounded`] these refreshes do
+/// nothing: rows already held by a `Bounded` cache are not re-read from the db.
+pub struct CacheDbActor<Conn, Table, C, P = NoPool<Conn>, K = NoIndex>
 where
     Conn: Connection + Unpin + 'static,
     Conn::Backend: HasSqlType<Table::SqlType>,
     Table: diesel::Table + HasTable<Table = Table> + AsQuery,
     Table::Query: QueryId + QueryFragment<Conn::Backend>,
     C: Cache<Conn, Table>,
+    P: PoolLike<Conn>,
 {
-    /// Connection for db
-    conn: Conn,
+    /// Where connections for db access come from
+    source: ConnSource<Conn, P>,
+    /// How much of the table to keep in memory
+    cache_size: CacheSize,
     /// All items read from db
     cache: Arc<HashMap<C::Id, C>>,
+    /// Recency order for `cache`, most recently used first. Only populated
+    /// (and consulted) under `CacheSize::Bounded`.
+    recency: VecDeque<C::Id>,
+    /// Highest cursor seen so far, used to fetch only newer rows on the
+    /// next timer refresh. `None` until the cache has been loaded once.
+    cursor: Option<C::Cursor>,
+    /// Timer ticks since the last full-table reconcile
+    ticks_since_reconcile: u32,
+    /// Do a full `read_all` reconcile every this many timer ticks, since a
+    /// pure watermark can't observe deletes
+    reconcile_every: u32,
+    /// Secondary index mapping an [`Indexed`] key to the ids of matching
+    /// rows. Cleared whenever the cache is reloaded or refreshed, and
+    /// rebuilt from the cache on the next `GetBy` that needs it. Empty and
+    /// unused unless `C: Indexed<Conn, Table, K>`.
+    indexes: HashMap<K, Vec<C::Id>>,
     /// Phantom marker for saving table inside structure
     t: PhantomData<Table>,
 }
@@ -95,6 +377,15 @@ impl<T: 'static> actix::Message for Save<T> {
     type Result = Result<()>;
 }
 
+/// Save several entries as a single transaction: either all of them land,
+/// or none do.
+#[derive(Debug)]
+pub struct SaveBatch<T>(pub Vec<T>);
+
+impl<T: 'static> actix::Message for SaveBatch<T> {
+    type Result = Result<()>;
+}
+
 /// Gets item by id
 #[derive(Debug)]
 pub struct Get<Conn, Table, C>
@@ -186,24 +477,41 @@ where
     type Result = Result<Arc<HashMap<C::Id, C>>>;
 }
 
-impl<Conn, Table, C> CacheDbActor<Conn, Table, C>
+impl<Conn, Table, C, P, K> CacheDbActor<Conn, Table, C, P, K>
 where
     Conn: Connection + Unpin + 'static,
     Conn::Backend: HasSqlType<Table::SqlType>,
     Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
     Table::Query: QueryId + QueryFragment<Conn::Backend>,
     C: Cache<Conn, Table>,
+    P: PoolLike<Conn>,
 {
-    /// Constructor
-    pub fn new(conn: Conn) -> Result<Self> {
-        let (cache, t) = Default::default();
-        let mut s = Self { conn, cache, t };
-        s.update()?;
-        Ok(s)
+    /// Run `f` with a `&Conn` obtained from this actor's connection source:
+    /// the owned connection itself, or one checked out of the pool.
+    fn with_conn<R>(&self, f: impl FnOnce(&Conn) -> Result<R>) -> Result<R> {
+        match &self.source {
+            ConnSource::Owned(conn) => f(conn),
+            ConnSource::Pool(pool) => {
+                let conn = pool.get()?;
+                f(&conn)
+            }
+        }
     }
 
+    /// Fully reload the cache from db. Used for the initial load and, under
+    /// `CacheSize::Unbounded`, to reconcile drift (e.g. deletes) that a
+    /// pure cursor watermark can't observe.
     fn update(&mut self) -> Result<()> {
-        self.cache = Arc::new(C::read_all(&self.conn)?);
+        if let CacheSize::Unbounded = self.cache_size {
+            let cache = self.with_conn(C::read_all)?;
+            self.cursor = cache.values().map(C::cursor).max();
+            self.cache = Arc::new(cache);
+            // Stale once the cache is replaced; rebuilt lazily on the next
+            // `GetBy` that needs it.
+            self.indexes.clear();
+        }
+        // `Disabled` never caches and `Bounded` loads lazily on each `Get`
+        // miss, so neither has a whole table to reload here.
         Ok(())
     }
 
@@ -211,20 +519,175 @@ where
         self.cache.get(&id).cloned()
     }
 
+    /// Look up `id` in a `Bounded` cache, marking it most recently used on
+    /// a hit.
+    fn touch(&mut self, id: &C::Id) -> Option<C> {
+        let out = self.cache.get(id).cloned();
+        if out.is_some() {
+            self.recency.retain(|cached| cached != id);
+            self.recency.push_front(id.clone());
+        }
+        out
+    }
+
+    /// Insert a freshly fetched row into a `Bounded` cache, evicting the
+    /// least recently used entry if that pushes the cache over `cap`.
+    fn insert_bounded(&mut self, id: C::Id, item: C, cap: usize) {
+        Arc::make_mut(&mut self.cache).insert(id.clone(), item);
+        self.recency.push_front(id);
+        while self.recency.len() > cap {
+            if let Some(evicted) = self.recency.pop_back() {
+                Arc::make_mut(&mut self.cache).remove(&evicted);
+            }
+        }
+    }
+}
+
+impl<Conn, Table, C, P, K> CacheDbActor<Conn, Table, C, P, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    // `timer_update` schedules itself via `TimerFunc`, which requires
+    // `Self: Actor` (`Context<Self>` + `.spawn(context)`), so this impl
+    // needs the full bound set `Actor for CacheDbActor<..>` needs below,
+    // not just the ones `incremental_update`'s own body touches.
+    P: PoolLike<Conn> + Unpin + 'static,
+    K: Unpin + 'static,
+    C::Cursor: AsExpression<<C::CursorColumn as Expression>::SqlType>,
+    Table: BoxedDsl<
+        'static,
+        Conn::Backend,
+        Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+    >,
+    Gt<C::CursorColumn, C::Cursor>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    Asc<C::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>: LoadQuery<Conn, C>,
+{
+    /// Apply only rows newer than the stored watermark instead of
+    /// reloading the whole table, reconciling with a full `update()` every
+    /// `reconcile_every` ticks (since a watermark alone can't see deletes).
+    fn incremental_update(&mut self) -> Result<()> {
+        self.ticks_since_reconcile += 1;
+        if self.ticks_since_reconcile >= self.reconcile_every {
+            self.ticks_since_reconcile = 0;
+            return self.update();
+        }
+
+        let (CacheSize::Unbounded, Some(cursor)) = (self.cache_size, self.cursor) else {
+            return self.update();
+        };
+        let fresh = self.with_conn(|conn| C::read_since(cursor, conn))?;
+        if fresh.is_empty() {
+            return Ok(());
+        }
+        let mut cache = (*self.cache).clone();
+        for item in fresh {
+            self.cursor = Some(self.cursor.map_or(item.cursor(), |c| c.max(item.cursor())));
+            cache.insert(item.get_id(), item);
+        }
+        self.cache = Arc::new(cache);
+        self.indexes.clear();
+        Ok(())
+    }
+
     fn timer_update(&mut self, context: &mut Context<Self>) {
         let dur = std::time::Duration::from_secs(60);
-        let _ = self.update();
+        let _ = self.incremental_update();
         TimerFunc::new(dur, Self::timer_update).spawn(context);
     }
 }
 
-impl<Conn, Table, C> Actor for CacheDbActor<Conn, Table, C>
+impl<Conn, Table, C, K> CacheDbActor<Conn, Table, C, NoPool<Conn>, K>
 where
     Conn: Connection + Unpin + 'static,
     Conn::Backend: HasSqlType<Table::SqlType>,
     Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
     Table::Query: QueryId + QueryFragment<Conn::Backend>,
     C: Cache<Conn, Table>,
+{
+    /// Constructor over a single, actor-owned connection.
+    ///
+    /// `reconcile_every` is how many timer ticks the incremental,
+    /// cursor-driven refresh takes between full-table reconciles (which
+    /// are the only way to observe deletes).
+    pub fn new(
+        conn: Conn,
+        cache_size: CacheSize,
+        reconcile_every: u32,
+    ) -> Result<Self> {
+        let (cache, recency, indexes, t) = Default::default();
+        let mut s = Self {
+            source: ConnSource::Owned(conn),
+            cache_size,
+            cache,
+            recency,
+            cursor: None,
+            ticks_since_reconcile: 0,
+            reconcile_every,
+            indexes,
+            t,
+        };
+        s.update()?;
+        Ok(s)
+    }
+}
+
+impl<Conn, Table, C, P, K> CacheDbActor<Conn, Table, C, P, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    P: PoolLike<Conn>,
+{
+    /// Constructor over a shared connection pool. Each `update()`, `Save`
+    /// and `Get` checks out its own connection, so several cache actors
+    /// (or cached reads mixed with uncached direct writes) can share the
+    /// same database without contending on one connection.
+    pub fn with_pool(
+        pool: P,
+        cache_size: CacheSize,
+        reconcile_every: u32,
+    ) -> Result<Self> {
+        let (cache, recency, indexes, t) = Default::default();
+        let mut s = Self {
+            source: ConnSource::Pool(pool),
+            cache_size,
+            cache,
+            recency,
+            cursor: None,
+            ticks_since_reconcile: 0,
+            reconcile_every,
+            indexes,
+            t,
+        };
+        s.update()?;
+        Ok(s)
+    }
+}
+
+impl<Conn, Table, C, P, K> Actor for CacheDbActor<Conn, Table, C, P, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    P: PoolLike<Conn> + Unpin + 'static,
+    K: Unpin + 'static,
+    C::Cursor: AsExpression<<C::CursorColumn as Expression>::SqlType>,
+    Table: BoxedDsl<
+        'static,
+        Conn::Backend,
+        Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+    >,
+    Gt<C::CursorColumn, C::Cursor>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    Asc<C::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>: LoadQuery<Conn, C>,
 {
     type Context = Context<Self>;
 
@@ -233,14 +696,27 @@ where
     }
 }
 
-impl<Conn, Table, C> Handler<GetAll<Conn, Table, C>>
-    for CacheDbActor<Conn, Table, C>
+impl<Conn, Table, C, P, K> Handler<GetAll<Conn, Table, C>>
+    for CacheDbActor<Conn, Table, C, P, K>
 where
     Conn: Connection + Unpin + 'static,
     Conn::Backend: HasSqlType<Table::SqlType>,
     Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
     Table::Query: QueryId + QueryFragment<Conn::Backend>,
     C: Cache<Conn, Table>,
+    // `Handler<M>: Actor`, so this impl needs every bound the `Actor` impl
+    // needs, not just the ones `handle()`'s own body touches.
+    P: PoolLike<Conn> + Unpin + 'static,
+    K: Unpin + 'static,
+    C::Cursor: AsExpression<<C::CursorColumn as Expression>::SqlType>,
+    Table: BoxedDsl<
+        'static,
+        Conn::Backend,
+        Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+    >,
+    Gt<C::CursorColumn, C::Cursor>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    Asc<C::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>: LoadQuery<Conn, C>,
 {
     type Result = Result<Arc<HashMap<C::Id, C>>>;
 
@@ -251,12 +727,16 @@ where
     ) -> Self::Result {
         // Flushing not by timer because we are not supposed to have error in
         // exported data.
+        //
+        // Under `CacheSize::Disabled` or `CacheSize::Bounded`, `update()` is
+        // a no-op, so this returns only whatever subset `Get` has lazily
+        // loaded so far rather than the whole table.
         self.update()?;
         Ok(Arc::clone(&self.cache))
     }
 }
 
-impl<Conn, Table, C, W> Handler<Save<W>> for CacheDbActor<Conn, Table, C>
+impl<Conn, Table, C, P, K, W> Handler<Save<W>> for CacheDbActor<Conn, Table, C, P, K>
 where
     Conn: Connection + Unpin + 'static,
     Conn::Backend: HasSqlType<Table::SqlType>,
@@ -264,6 +744,19 @@ where
     Table::Query: QueryId + QueryFragment<Conn::Backend>,
     Table::FromClause: QueryFragment<Conn::Backend>,
     C: Cache<Conn, Table>,
+    // `Handler<M>: Actor`, so this impl needs every bound the `Actor` impl
+    // needs, not just the ones `handle()`'s own body touches.
+    P: PoolLike<Conn> + Unpin + 'static,
+    K: Unpin + 'static,
+    C::Cursor: AsExpression<<C::CursorColumn as Expression>::SqlType>,
+    Table: BoxedDsl<
+        'static,
+        Conn::Backend,
+        Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+    >,
+    Gt<C::CursorColumn, C::Cursor>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    Asc<C::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>: LoadQuery<Conn, C>,
     W: Insertable<Table> + 'static,
     W::Values:
         CanInsertInSingleQuery<Conn::Backend> + QueryFragment<Conn::Backend>,
@@ -271,20 +764,78 @@ where
     type Result = Result<()>;
 
     fn handle(&mut self, pred: Save<W>, _: &mut Context<Self>) -> Self::Result {
-        C::write_one(pred.0, &self.conn)?;
+        self.with_conn(|conn| C::write_one(pred.0, conn))?;
+        self.update()?;
+        Ok(())
+    }
+}
+
+impl<Conn, Table, C, P, K, W> Handler<SaveBatch<W>> for CacheDbActor<Conn, Table, C, P, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    Table::FromClause: QueryFragment<Conn::Backend>,
+    C: Cache<Conn, Table>,
+    // `Handler<M>: Actor`, so this impl needs every bound the `Actor` impl
+    // needs, not just the ones `handle()`'s own body touches.
+    P: PoolLike<Conn> + Unpin + 'static,
+    K: Unpin + 'static,
+    C::Cursor: AsExpression<<C::CursorColumn as Expression>::SqlType>,
+    Table: BoxedDsl<
+        'static,
+        Conn::Backend,
+        Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+    >,
+    Gt<C::CursorColumn, C::Cursor>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    Asc<C::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>: LoadQuery<Conn, C>,
+    W: Insertable<Table> + 'static,
+    W::Values:
+        CanInsertInSingleQuery<Conn::Backend> + QueryFragment<Conn::Backend>,
+{
+    type Result = Result<()>;
+
+    fn handle(&mut self, batch: SaveBatch<W>, _: &mut Context<Self>) -> Self::Result {
+        self.with_conn(|conn| {
+            conn.transaction(|| {
+                for w in batch.0 {
+                    C::write_one(w, conn)?;
+                }
+                Ok(())
+            })
+        })?;
         self.update()?;
         Ok(())
     }
 }
 
-impl<Conn, Table, C> Handler<Get<Conn, Table, C>>
-    for CacheDbActor<Conn, Table, C>
+impl<Conn, Table, C, P, K> Handler<Get<Conn, Table, C>>
+    for CacheDbActor<Conn, Table, C, P, K>
 where
     Conn: Connection + Unpin + 'static,
     Conn::Backend: HasSqlType<Table::SqlType>,
     Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
     Table::Query: QueryId + QueryFragment<Conn::Backend>,
     C: Cache<Conn, Table>,
+    // `Handler<M>: Actor`, so this impl needs every bound the `Actor` impl
+    // needs, not just the ones `handle()`'s own body touches.
+    P: PoolLike<Conn> + Unpin + 'static,
+    K: Unpin + 'static,
+    C::Cursor: AsExpression<<C::CursorColumn as Expression>::SqlType>,
+    Table: BoxedDsl<
+        'static,
+        Conn::Backend,
+        Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+    >,
+    Gt<C::CursorColumn, C::Cursor>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    Asc<C::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>: LoadQuery<Conn, C>,
+    Table: FindDsl<C::Id>,
+    Find<Table, C::Id>: LimitDsl,
+    Limit<Find<Table, C::Id>>:
+        QueryFragment<Conn::Backend> + QueryId + LoadQuery<Conn, C>,
 {
     type Result = Result<Option<C>>;
 
@@ -293,12 +844,150 @@ where
         Get { id }: Get<Conn, Table, C>,
         _: &mut Context<Self>,
     ) -> Self::Result {
-        match self.get(id.clone()) {
-            Some(out) => Ok(Some(out)),
-            None => {
-                self.update()?;
-                Ok(self.get(id))
+        match self.cache_size {
+            CacheSize::Disabled => {
+                self.with_conn(|conn| C::read_one(id, conn))
             }
+            CacheSize::Unbounded => match self.get(id.clone()) {
+                Some(out) => Ok(Some(out)),
+                None => {
+                    self.update()?;
+                    Ok(self.get(id))
+                }
+            },
+            CacheSize::Bounded(cap) => {
+                if let Some(out) = self.touch(&id) {
+                    return Ok(Some(out));
+                }
+                let fetched = self.with_conn(|conn| C::read_one(id.clone(), conn))?;
+                if let Some(item) = &fetched {
+                    self.insert_bounded(id, item.clone(), cap);
+                }
+                Ok(fetched)
+            }
+        }
+    }
+}
+
+impl<Conn, Table, C, P, K> CacheDbActor<Conn, Table, C, P, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Indexed<Conn, Table, K>,
+    P: PoolLike<Conn>,
+    K: Hash + Eq + Clone,
+{
+    /// Rebuild the `K -> [Id]` index from the current cache.
+    fn rebuild_index(&mut self) {
+        let mut indexes: HashMap<K, Vec<C::Id>> = HashMap::new();
+        for (id, item) in self.cache.iter() {
+            indexes.entry(item.index_key()).or_default().push(id.clone());
         }
+        self.indexes = indexes;
+    }
+
+    /// Resolve `key` against the index, building it from the cache first
+    /// if it hasn't been built yet.
+    ///
+    /// Returns `None` (never trusting a partial answer) unless
+    /// `self.cache` is guaranteed to mirror the whole table, i.e. under
+    /// `CacheSize::Unbounded`. Under `Bounded`/`Disabled` the cache only
+    /// ever holds an arbitrary subset of rows, so an index built from it
+    /// would silently under-report matches.
+    fn lookup_index(&mut self, key: &K) -> Option<Vec<C>> {
+        if !cache_is_complete(self.cache_size) {
+            return None;
+        }
+        if self.indexes.is_empty() && !self.cache.is_empty() {
+            self.rebuild_index();
+        }
+        self.indexes.get(key).map(|ids| {
+            ids.iter().filter_map(|id| self.cache.get(id).cloned()).collect()
+        })
+    }
+}
+
+impl<Conn, Table, C, P, K> Handler<GetBy<Conn, Table, C, K>>
+    for CacheDbActor<Conn, Table, C, P, K>
+where
+    Conn: Connection + Unpin + 'static,
+    Conn::Backend: HasSqlType<Table::SqlType>,
+    Table: diesel::Table + HasTable<Table = Table> + AsQuery + Unpin + 'static,
+    Table::Query: QueryId + QueryFragment<Conn::Backend>,
+    C: Indexed<Conn, Table, K>,
+    // `Handler<M>: Actor`, so this impl needs every bound the `Actor` impl
+    // needs, not just the ones `handle()`'s own body touches.
+    P: PoolLike<Conn> + Unpin + 'static,
+    K: Unpin + 'static,
+    C::Cursor: AsExpression<<C::CursorColumn as Expression>::SqlType>,
+    Table: BoxedDsl<
+        'static,
+        Conn::Backend,
+        Output = BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>,
+    >,
+    Gt<C::CursorColumn, C::Cursor>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    Asc<C::CursorColumn>: AppearsOnTable<Table> + QueryFragment<Conn::Backend>,
+    BoxedSelectStatement<'static, Table::SqlType, Table, Conn::Backend>: LoadQuery<Conn, C>,
+    K: Hash + Eq + Clone,
+    K: AsExpression<<C::KeyColumn as Expression>::SqlType>,
+    Table: FilterDsl<diesel::dsl::Eq<C::KeyColumn, K>>,
+    Filter<Table, diesel::dsl::Eq<C::KeyColumn, K>>:
+        QueryFragment<Conn::Backend> + QueryId + LoadQuery<Conn, C>,
+{
+    type Result = Result<Vec<C>>;
+
+    fn handle(
+        &mut self,
+        GetBy { key, .. }: GetBy<Conn, Table, C, K>,
+        _: &mut Context<Self>,
+    ) -> Self::Result {
+        if !cache_is_complete(self.cache_size) {
+            // `Bounded`/`Disabled` caches never hold the whole table, so
+            // the in-memory index can't be trusted here; go straight to
+            // the db, same as `Get` does under `CacheSize::Disabled`.
+            return self.with_conn(|conn| C::read_by(key, conn));
+        }
+        if let Some(out) = index_hit(self.lookup_index(&key)) {
+            return Ok(out);
+        }
+        // Index miss: the row may just not be cached yet, so refresh and
+        // retry before falling back to a direct db query.
+        self.update()?;
+        self.rebuild_index();
+        match index_hit(self.lookup_index(&key)) {
+            Some(out) => Ok(out),
+            None => self.with_conn(|conn| C::read_by(key, conn)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Regression test for a bug where `GetBy` trusted an index built from
+    // `self.cache` even under `CacheSize::Bounded`/`Disabled`, where the
+    // cache only ever holds a subset of the table — silently returning
+    // incomplete results instead of falling back to the db.
+    #[test]
+    fn only_unbounded_cache_is_trusted_for_get_by() {
+        assert!(cache_is_complete(CacheSize::Unbounded));
+        assert!(!cache_is_complete(CacheSize::Disabled));
+        assert!(!cache_is_complete(CacheSize::Bounded(10)));
+    }
+
+    // `Handler<GetBy<_>>::handle` relies on `index_hit` to tell a real,
+    // trustworthy match apart from "nothing indexed for this key" and "the
+    // index hasn't been (re)built yet" — both of which come back from
+    // `lookup_index` as something other than a nonempty `Some`, and both of
+    // which must fall through to a direct db query rather than being
+    // reported as "zero matches".
+    #[test]
+    fn index_hit_ignores_missing_or_stale_lookups() {
+        assert_eq!(index_hit(Some(vec![1, 2])), Some(vec![1, 2]));
+        assert_eq!(index_hit(Some(Vec::<i32>::new())), None);
+        assert_eq!(index_hit(None::<Vec<i32>>), None);
     }
 }